@@ -1,27 +1,81 @@
+use clap::{Parser, Subcommand};
 use config::{Config, ConfigError, Environment, File};
 use quick_xml::events::{BytesText, Event};
 use quick_xml::{Reader, Writer};
 use regex::Regex;
-use serde::{Deserialize, Serialize};
-use std::io::Cursor;
-use tokio;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashSet;
+use std::io::{Cursor, Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 use tracing::{info, error};
 use warp::Filter;
 
+/// RSS标题转换与多源聚合工具。
+#[derive(Debug, Parser)]
+#[command(name = "arrs-rss-converter", about = "RSS标题转换与多源聚合工具")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// 启动HTTP服务（默认行为，省略子命令等价于此项）
+    Serve,
+    /// 离线转换：从文件或stdin读取一份RSS，转换标题后写入stdout
+    Convert {
+        /// RSS源文件路径；省略则从stdin读取
+        #[arg(long)]
+        input: Option<PathBuf>,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// When set, `/rss.xml` and `/health` require a matching `?secret=` query parameter or
+    /// `X-API-Secret` header. Left unset (the default), the endpoints stay open.
+    #[serde(default)]
+    pub api_secret: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RssConfig {
-    pub source_url: String,
+    #[serde(alias = "source_url", deserialize_with = "deserialize_source_urls")]
+    pub source_urls: Vec<String>,
+}
+
+// 兼容旧版单一 `source_url` 字段：既接受单个字符串，也接受字符串数组。
+fn deserialize_source_urls<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(url) => Ok(vec![url]),
+        OneOrMany::Many(urls) => Ok(urls),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversionConfig {
     pub default_priority: u32,
+    /// Drop items whose converted title matches this regex, if set.
+    #[serde(default)]
+    pub exclude_pattern: Option<String>,
+    /// Keep only the first N items (after filtering and priority sorting), if set.
+    #[serde(default)]
+    pub max_items: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,12 +83,20 @@ pub struct LoggingConfig {
     pub level: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    pub cache_ttl_secs: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub rss: RssConfig,
     pub conversion: ConversionConfig,
     pub logging: LoggingConfig,
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub rules: Vec<TitleRule>,
 }
 
 impl Default for AppConfig {
@@ -43,16 +105,23 @@ impl Default for AppConfig {
             server: ServerConfig {
                 host: "127.0.0.1".to_string(),
                 port: 3030,
+                api_secret: None,
             },
             rss: RssConfig {
-                source_url: "https://example.com/rss.xml".to_string(),
+                source_urls: vec!["https://example.com/rss.xml".to_string()],
             },
             conversion: ConversionConfig {
                 default_priority: 100,
+                exclude_pattern: None,
+                max_items: None,
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
             },
+            cache: CacheConfig {
+                cache_ttl_secs: 300,
+            },
+            rules: vec![default_conan_rule()],
         }
     }
 }
@@ -65,6 +134,7 @@ impl AppConfig {
         let mut builder = Config::builder()
             .add_source(config::Config::try_from(&default_config)?)
             .add_source(File::with_name("config").required(false))
+            .add_source(File::with_name("rules").required(false))
             .add_source(Environment::with_prefix("RSS_CONVERTER").separator("_"));
 
         // 支持直接的环境变量覆盖
@@ -77,12 +147,24 @@ impl AppConfig {
         if let Ok(port) = std::env::var("SERVER_PORT") {
             builder = builder.set_override("server.port", port)?;
         }
+        if let Ok(secret) = std::env::var("SERVER_API_SECRET") {
+            builder = builder.set_override("server.api_secret", secret)?;
+        }
         if let Ok(priority) = std::env::var("CONVERSION_DEFAULT_PRIORITY") {
             builder = builder.set_override("conversion.default_priority", priority)?;
         }
+        if let Ok(pattern) = std::env::var("CONVERSION_EXCLUDE_PATTERN") {
+            builder = builder.set_override("conversion.exclude_pattern", pattern)?;
+        }
+        if let Ok(max_items) = std::env::var("CONVERSION_MAX_ITEMS") {
+            builder = builder.set_override("conversion.max_items", max_items)?;
+        }
         if let Ok(level) = std::env::var("LOGGING_LEVEL") {
             builder = builder.set_override("logging.level", level)?;
         }
+        if let Ok(ttl) = std::env::var("CACHE_TTL_SECS") {
+            builder = builder.set_override("cache.cache_ttl_secs", ttl)?;
+        }
 
         let config = builder.build()?;
         config.try_deserialize()
@@ -110,22 +192,30 @@ struct CompiledRule {
     priority: u32,
 }
 
+// 柯南标题规则，现在作为AppConfig默认的rules条目，可通过配置覆盖
+pub fn default_conan_rule() -> TitleRule {
+    TitleRule {
+        name: "Detective Conan".to_string(),
+        pattern: r"\[([^\]]+)\]\[名侦探柯南\]\[第(\d+)集\s+([^]]+)\]\[([^]]+)\]\[([^]]+)\](?:\[([^]]+)\])?\[([^]]+)\]".to_string(),
+        replacement: " [$1] Detective Conan - $2 ($4 $7 $5) ".to_string(),
+        priority: 1,
+    }
+}
+
 impl TitleConverter {
     pub fn new() -> Self {
-        let mut converter = TitleConverter { rules: Vec::new() };
-        
-        // 添加名侦探柯南的转换规则
-        let conan_rule = TitleRule {
-            name: "Detective Conan".to_string(),
-            pattern: r"\[([^\]]+)\]\[名侦探柯南\]\[第(\d+)集\s+([^]]+)\]\[([^]]+)\]\[([^]]+)\](?:\[([^]]+)\])?\[([^]]+)\]".to_string(),
-            replacement: " [$1] Detective Conan - $2 ($4 $7 $5) ".to_string(),
-            priority: 1,
-        };
-        
-        converter.add_rule(conan_rule);
+        TitleConverter { rules: Vec::new() }
+    }
+
+    /// Build a converter from a set of declaratively configured rules, e.g. `AppConfig::rules`.
+    pub fn from_rules(rules: Vec<TitleRule>) -> Self {
+        let mut converter = TitleConverter::new();
+        for rule in rules {
+            converter.add_rule(rule);
+        }
         converter
     }
-    
+
     pub fn add_rule(&mut self, rule: TitleRule) {
         match Regex::new(&rule.pattern) {
             Ok(regex) => {
@@ -147,105 +237,331 @@ impl TitleConverter {
     }
     
     pub fn convert_title(&self, original: &str) -> String {
+        self.convert_title_with_priority(original, 0).0
+    }
+
+    /// Same as `convert_title`, but also returns the priority of the rule that matched (or
+    /// `default_priority` if none did). Used to order items when re-serializing a feed.
+    pub fn convert_title_with_priority(&self, original: &str, default_priority: u32) -> (String, u32) {
         for rule in &self.rules {
             if let Some(captures) = rule.regex.captures(original) {
-                let mut result = rule.replacement.clone();
-                
-                // 替换捕获组
-                for i in 0..captures.len() {
-                    let placeholder = format!("${}", i);
-                    if let Some(capture) = captures.get(i) {
-                        result = result.replace(&placeholder, capture.as_str());
-                    }
-                }
-                
+                // 使用regex原生的Captures::expand替换捕获组，支持`$name`/`${name}`命名捕获组
+                // 以及`$0`..`$N`数字占位符，避免替换文本本身包含`$数字`时产生歧义。
+                let mut result = String::new();
+                captures.expand(&rule.replacement, &mut result);
+
                 info!("Title converted by rule '{}': {} -> {}", rule.name, original, result);
-                return result;
+                return (result, rule.priority);
             }
         }
-        
-        // 如果没有匹配的规则，返回原标题
-        original.to_string()
+
+        // 如果没有匹配的规则，返回原标题，并使用调用方提供的默认优先级
+        (original.to_string(), default_priority)
+    }
+}
+
+// 带时间戳的响应缓存，用于TTL校验
+#[derive(Debug, Clone)]
+pub struct CachedFeed {
+    pub body: String,
+    pub fetched_at: Instant,
+}
+
+impl CachedFeed {
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        self.fetched_at.elapsed() < ttl
+    }
+}
+
+/// Shared response cache: `None` means nothing has been fetched yet.
+pub type FeedCache = Arc<RwLock<Option<CachedFeed>>>;
+
+// 聚合所有配置的RSS源：各自抓取解析后合并去重，再统一过滤/排序/截断
+pub async fn fetch_and_convert_rss(urls: &[String], converter: &TitleConverter, conversion: &ConversionConfig) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if urls.is_empty() {
+        return Err("no RSS source_urls configured".into());
+    }
+
+    let fetches = urls.iter().map(|url| fetch_channel(url));
+    let fetched = futures::future::join_all(fetches).await;
+
+    let mut channels = Vec::new();
+    for (url, result) in urls.iter().zip(fetched) {
+        match result {
+            Ok(channel) => channels.push(channel),
+            Err(e) => error!("Skipping source {} due to fetch error: {}", url, e),
+        }
     }
+
+    if channels.is_empty() {
+        return Err("all RSS sources failed to fetch".into());
+    }
+
+    let merged = merge_channels(channels);
+    let output = render_channel(merged, converter, conversion)?;
+
+    info!("RSS aggregation completed: {} source(s)", urls.len());
+    Ok(output)
 }
 
-pub async fn fetch_and_convert_rss(url: &str, converter: &TitleConverter) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+async fn fetch_channel(url: &str) -> Result<Channel, Box<dyn std::error::Error + Send + Sync>> {
     info!("Fetching RSS from: {}", url);
-    
+
     // 获取原始RSS内容
     let response = reqwest::get(url).await?;
     let rss_content = response.text().await?;
-    
-    // 使用quick-xml处理RSS，保留原始格式
-    let mut reader = Reader::from_str(&rss_content);
+
+    Channel::parse(&rss_content)
+}
+
+// 合并多个频道：保留第一个频道的头尾，按guid/link去重拼接所有条目
+fn merge_channels(mut channels: Vec<Channel>) -> Channel {
+    let first = channels.remove(0);
+    let preamble = first.preamble.clone();
+    let footer = first.footer.clone();
+
+    let mut seen_keys: HashSet<String> = HashSet::new();
+    let mut items = Vec::new();
+    for channel in std::iter::once(first).chain(channels) {
+        for item in channel.items {
+            let key = item
+                .guid
+                .as_ref()
+                .map(|guid| format!("guid:{}", guid))
+                .or_else(|| item.link.as_ref().map(|link| format!("link:{}", link)))
+                .unwrap_or_else(|| item.original_title.clone());
+
+            if seen_keys.insert(key) {
+                items.push(item);
+            }
+        }
+    }
+
+    Channel { preamble, items, footer }
+}
+
+struct FeedParts {
+    preamble: String,
+    items: Vec<String>,
+    footer: String,
+}
+
+// 拆分RSS文档为头部/各item/尾部
+fn split_feed(rss_content: &str) -> Result<FeedParts, Box<dyn std::error::Error + Send + Sync>> {
+    let mut reader = Reader::from_str(rss_content);
     reader.trim_text(true);
-    
+
+    let mut preamble = Writer::new(Cursor::new(Vec::new()));
+    let mut items = Vec::new();
+    let mut tail = Writer::new(Cursor::new(Vec::new()));
+
+    let mut seen_item = false;
+    let mut item_depth = 0usize;
+    let mut current_item = Writer::new(Cursor::new(Vec::new()));
+    let mut buf = Vec::new();
+
+    macro_rules! sink {
+        () => {
+            if item_depth > 0 {
+                &mut current_item
+            } else if seen_item {
+                &mut tail
+            } else {
+                &mut preamble
+            }
+        };
+    }
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                if e.name().as_ref() == b"item" {
+                    if item_depth == 0 {
+                        // Starting a fresh item discards whatever `tail` buffered since the
+                        // previous item, so content between two items is dropped.
+                        tail = Writer::new(Cursor::new(Vec::new()));
+                        current_item = Writer::new(Cursor::new(Vec::new()));
+                    }
+                    item_depth += 1;
+                }
+                sink!().write_event(Event::Start(e.clone()))?;
+            }
+            Ok(Event::End(ref e)) => {
+                sink!().write_event(Event::End(e.clone()))?;
+                if e.name().as_ref() == b"item" {
+                    item_depth -= 1;
+                    if item_depth == 0 {
+                        seen_item = true;
+                        let bytes = current_item.into_inner().into_inner();
+                        items.push(String::from_utf8(bytes)?);
+                        current_item = Writer::new(Cursor::new(Vec::new()));
+                    }
+                }
+            }
+            Ok(Event::Text(ref e)) => sink!().write_event(Event::Text(e.clone()))?,
+            Ok(Event::CData(ref e)) => sink!().write_event(Event::CData(e.clone()))?,
+            Ok(Event::Empty(ref e)) => sink!().write_event(Event::Empty(e.clone()))?,
+            Ok(Event::Comment(ref e)) => sink!().write_event(Event::Comment(e.clone()))?,
+            Ok(Event::Decl(ref e)) => sink!().write_event(Event::Decl(e.clone()))?,
+            Ok(Event::PI(ref e)) => sink!().write_event(Event::PI(e.clone()))?,
+            Ok(Event::DocType(ref e)) => sink!().write_event(Event::DocType(e.clone()))?,
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                error!("Error reading XML: {}", e);
+                break;
+            }
+        }
+        buf.clear();
+    }
+
+    Ok(FeedParts {
+        preamble: String::from_utf8(preamble.into_inner().into_inner())?,
+        items,
+        footer: String::from_utf8(tail.into_inner().into_inner())?,
+    })
+}
+
+/// The `<title>`/`<guid>`/`<link>` text pulled out of a serialized `<item>` block.
+#[derive(Debug, Default, Clone)]
+struct ItemFields {
+    title: String,
+    guid: Option<String>,
+    link: Option<String>,
+}
+
+// 从item XML中提取title/guid/link文本
+fn parse_item_fields(item_xml: &str) -> ItemFields {
+    let mut reader = Reader::from_str(item_xml);
+    reader.trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut inside: Option<&'static str> = None;
+    let mut title = String::new();
+    let mut guid = String::new();
+    let mut link = String::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                inside = match e.name().as_ref() {
+                    b"title" => Some("title"),
+                    b"guid" => Some("guid"),
+                    b"link" => Some("link"),
+                    _ => None,
+                };
+            }
+            Ok(Event::Text(ref e)) => {
+                let text = e.unescape().map(|s| s.to_string()).unwrap_or_default();
+                match inside {
+                    Some("title") => title.push_str(&text),
+                    Some("guid") => guid.push_str(&text),
+                    Some("link") => link.push_str(&text),
+                    _ => {}
+                }
+            }
+            Ok(Event::CData(ref e)) => {
+                let text = String::from_utf8_lossy(e).to_string();
+                match inside {
+                    Some("title") => title.push_str(&text),
+                    Some("guid") => guid.push_str(&text),
+                    Some("link") => link.push_str(&text),
+                    _ => {}
+                }
+            }
+            Ok(Event::End(_)) => inside = None,
+            Ok(Event::Eof) => break,
+            Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    ItemFields {
+        title,
+        guid: (!guid.is_empty()).then_some(guid),
+        link: (!link.is_empty()).then_some(link),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub original_title: String,
+    pub guid: Option<String>,
+    pub link: Option<String>,
+    raw: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub preamble: String,
+    pub items: Vec<Item>,
+    pub footer: String,
+}
+
+impl Channel {
+    pub fn parse(rss_content: &str) -> Result<Channel, Box<dyn std::error::Error + Send + Sync>> {
+        let parts = split_feed(rss_content)?;
+        let items = parts
+            .items
+            .into_iter()
+            .map(|raw| {
+                let fields = parse_item_fields(&raw);
+                Item {
+                    original_title: fields.title,
+                    guid: fields.guid,
+                    link: fields.link,
+                    raw,
+                }
+            })
+            .collect();
+
+        Ok(Channel {
+            preamble: parts.preamble,
+            items,
+            footer: parts.footer,
+        })
+    }
+}
+
+// 重写单个item块的title文本，其余元素保持不变
+fn rewrite_item_title(item_xml: &str, new_title: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let mut reader = Reader::from_str(item_xml);
+    reader.trim_text(true);
+
     let mut writer = Writer::new(Cursor::new(Vec::new()));
     let mut buf = Vec::new();
     let mut inside_title = false;
-    let mut inside_item = false;
-    let mut current_title = String::new();
-    
+
     loop {
         match reader.read_event_into(&mut buf) {
             Ok(Event::Start(ref e)) => {
-                let name = e.name();
-                if name.as_ref() == b"item" {
-                    inside_item = true;
-                    info!("Processing RSS item");
-                }
-                if name.as_ref() == b"title" && inside_item {
+                if e.name().as_ref() == b"title" {
                     inside_title = true;
-                    current_title.clear();
                 }
                 writer.write_event(Event::Start(e.clone()))?;
             }
             Ok(Event::End(ref e)) => {
-                let name = e.name();
-                if name.as_ref() == b"item" {
-                    inside_item = false;
-                }
-                if name.as_ref() == b"title" && inside_title {
+                if e.name().as_ref() == b"title" && inside_title {
                     inside_title = false;
-                    // 转换标题并写入
-                    let converted_title = converter.convert_title(&current_title);
-                    
-                    // 直接写入转换后的标题作为文本
-                    writer.write_event(Event::Text(BytesText::new(&converted_title)))?;
+                    writer.write_event(Event::Text(BytesText::new(new_title)))?;
                 }
                 writer.write_event(Event::End(e.clone()))?;
             }
             Ok(Event::Text(ref e)) => {
-                if inside_title && inside_item {
-                    current_title.push_str(&e.unescape().unwrap_or_default());
-                } else {
+                if !inside_title {
                     writer.write_event(Event::Text(e.clone()))?;
                 }
             }
             Ok(Event::CData(ref e)) => {
-                if inside_title && inside_item {
-                    // CDATA内容，去掉CDATA标记获取实际内容
-                    let cdata_content = String::from_utf8_lossy(e);
-                    current_title.push_str(&cdata_content);
-                } else {
+                if !inside_title {
                     writer.write_event(Event::CData(e.clone()))?;
                 }
             }
-            Ok(Event::Empty(ref e)) => {
-                writer.write_event(Event::Empty(e.clone()))?;
-            }
-            Ok(Event::Comment(ref e)) => {
-                writer.write_event(Event::Comment(e.clone()))?;
-            }
-            Ok(Event::Decl(ref e)) => {
-                writer.write_event(Event::Decl(e.clone()))?;
-            }
-            Ok(Event::PI(ref e)) => {
-                writer.write_event(Event::PI(e.clone()))?;
-            }
-            Ok(Event::DocType(ref e)) => {
-                writer.write_event(Event::DocType(e.clone()))?;
-            }
+            Ok(Event::Empty(ref e)) => writer.write_event(Event::Empty(e.clone()))?,
+            Ok(Event::Comment(ref e)) => writer.write_event(Event::Comment(e.clone()))?,
+            Ok(Event::Decl(ref e)) => writer.write_event(Event::Decl(e.clone()))?,
+            Ok(Event::PI(ref e)) => writer.write_event(Event::PI(e.clone()))?,
+            Ok(Event::DocType(ref e)) => writer.write_event(Event::DocType(e.clone()))?,
             Ok(Event::Eof) => break,
             Err(e) => {
                 error!("Error reading XML: {}", e);
@@ -255,79 +571,240 @@ pub async fn fetch_and_convert_rss(url: &str, converter: &TitleConverter) -> Res
         buf.clear();
     }
 
-    let result = writer.into_inner().into_inner();
-    let output = String::from_utf8(result)?;
-    
-    info!("RSS conversion completed");
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
+// (converted_title, priority, item), as produced by convert_channel_items
+type ConvertedItems = Vec<(String, u32, Item)>;
+
+// 仅做标题转换，不做过滤/排序/截断，以便聚合时先合并去重再统一应用策略
+fn convert_channel_items(items: Vec<Item>, converter: &TitleConverter, default_priority: u32) -> ConvertedItems {
+    items
+        .into_iter()
+        .map(|item| {
+            let (converted_title, priority) =
+                converter.convert_title_with_priority(&item.original_title, default_priority);
+            (converted_title, priority, item)
+        })
+        .collect()
+}
+
+// 对已转换标题的条目统一应用exclude_pattern/排序/max_items，只能整体调用一次
+fn apply_conversion_policy(mut items: ConvertedItems, conversion: &ConversionConfig) -> Result<ConvertedItems, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(pattern) = &conversion.exclude_pattern {
+        let re = Regex::new(pattern)?;
+        items.retain(|(converted_title, _priority, _item)| {
+            let excluded = re.is_match(converted_title);
+            if excluded {
+                info!("Excluding item '{}' (matched exclude_pattern)", converted_title);
+            }
+            !excluded
+        });
+    }
+
+    // 按规则优先级排序：数字越小优先级越高，与TitleConverter.add_rule的排序一致
+    items.sort_by_key(|(_, priority, _)| *priority);
+
+    if let Some(max_items) = conversion.max_items {
+        items.truncate(max_items);
+    }
+
+    Ok(items)
+}
+
+fn render_channel(channel: Channel, converter: &TitleConverter, conversion: &ConversionConfig) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let converted = convert_channel_items(channel.items, converter, conversion.default_priority);
+    let items = apply_conversion_policy(converted, conversion)?;
+
+    let mut output = channel.preamble;
+    for (converted_title, _priority, item) in &items {
+        output.push_str(&rewrite_item_title(&item.raw, converted_title)?);
+    }
+    output.push_str(&channel.footer);
+
+    info!("RSS conversion completed: {} item(s) kept", items.len());
     Ok(output)
 }
 
+// 单源的转换入口，供convert子命令使用；多源场景见fetch_and_convert_rss
+pub fn convert_rss_text(rss_content: &str, converter: &TitleConverter, conversion: &ConversionConfig) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let channel = Channel::parse(rss_content)?;
+    render_channel(channel, converter, conversion)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // 加载环境变量
     dotenvy::dotenv().ok();
-    
+
+    let cli = Cli::parse();
+
     // 加载配置
     let config = AppConfig::load().unwrap_or_else(|err| {
         eprintln!("Error loading config: {}", err);
         eprintln!("Using default configuration");
         AppConfig::default()
     });
-    
+
     // 初始化日志
     tracing_subscriber::fmt()
         .with_max_level(config.logging.level.parse().unwrap_or(tracing::Level::INFO))
         .init();
-    
+
+    match cli.command.unwrap_or(Commands::Serve) {
+        Commands::Serve => serve(config).await,
+        Commands::Convert { input } => convert_cli(config, input),
+    }
+}
+
+async fn serve(config: AppConfig) -> Result<(), Box<dyn std::error::Error>> {
     info!("Starting RSS converter service");
-    info!("Configuration loaded: RSS source = {}", config.rss.source_url);
-    
-    let converter = TitleConverter::new();
-    
+    info!("Configuration loaded: RSS sources = {:?}", config.rss.source_urls);
+
+    let converter = TitleConverter::from_rules(config.rules.clone());
+
     // 测试转换功能
     let test_title = "[银色子弹字幕组][名侦探柯南][第1170集 食人教室的玄机（后篇）][WEBRIP][简繁日多语MKV][PGS][1080P]";
     let converted = converter.convert_title(test_title);
     info!("Test conversion - Original: {}", test_title);
     info!("Test conversion - Converted: {}", converted);
-    
-    // 创建共享配置和转换器
-    let config_clone = config.clone();
-    let app_config = warp::any().map(move || config_clone.clone());
+
+    // 创建共享响应缓存
+    let cache: FeedCache = Arc::new(RwLock::new(None));
+    let routes = build_routes(config.clone(), converter, cache);
+
+    let addr = (config.server.host.parse::<std::net::IpAddr>().unwrap_or([127, 0, 0, 1].into()), config.server.port);
+    info!("RSS转换服务启动在 http://{}:{}", config.server.host, config.server.port);
+    info!("使用方法: http://{}:{}/rss.xml", config.server.host, config.server.port);
+    info!("健康检查: http://{}:{}/health", config.server.host, config.server.port);
+
+    warp::serve(routes)
+        .run(addr)
+        .await;
+
+    Ok(())
+}
+
+// 路由表：/rss.xml和/health，均受api_secret保护；拆出来方便测试直接调用
+fn build_routes(
+    config: AppConfig,
+    converter: TitleConverter,
+    cache: FeedCache,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = std::convert::Infallible> + Clone {
+    let api_secret = config.server.api_secret.clone();
+    let config_filter = warp::any().map(move || config.clone());
     let converter_filter = warp::any().map(move || converter.clone());
-    
+    let cache_filter = warp::any().map(move || cache.clone());
+
     // 创建Web服务路由 - 不再需要URL参数
     let convert_route = warp::path("rss.xml")
         .and(warp::get())
-        .and(app_config)
+        .and(require_api_secret(api_secret.clone()))
+        .and(config_filter)
         .and(converter_filter)
+        .and(cache_filter)
         .and_then(handle_convert_request);
-    
+
     // 健康检查端点
     let health_route = warp::path("health")
         .and(warp::get())
+        .and(require_api_secret(api_secret))
         .map(|| "OK");
-    
-    let routes = convert_route.or(health_route);
-    
-    let addr = (config.server.host.parse::<std::net::IpAddr>().unwrap_or([127, 0, 0, 1].into()), config.server.port);
-    info!("RSS转换服务启动在 http://{}:{}", config.server.host, config.server.port);
-    info!("使用方法: http://{}:{}/rss.xml", config.server.host, config.server.port);
-    info!("健康检查: http://{}:{}/health", config.server.host, config.server.port);
-    
-    warp::serve(routes)
-        .run(addr)
-        .await;
-    
+
+    convert_route.or(health_route).recover(handle_rejection)
+}
+
+// convert子命令：从input（或stdin）读取一个feed，转换标题后打印到stdout
+fn convert_cli(config: AppConfig, input: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let reader: Box<dyn Read> = match input {
+        Some(path) => Box::new(std::fs::File::open(&path)?),
+        None => Box::new(std::io::stdin()),
+    };
+    let rss_content = read_to_string(reader)?;
+
+    let converter = TitleConverter::from_rules(config.rules.clone());
+    let converted = convert_rss_text(&rss_content, &converter, &config.conversion)
+        .map_err(|e| -> Box<dyn std::error::Error> { e.to_string().into() })?;
+
+    std::io::stdout().write_all(converted.as_bytes())?;
     Ok(())
 }
 
+fn read_to_string(mut reader: impl Read) -> std::io::Result<String> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+#[derive(Debug)]
+struct InvalidApiSecret;
+
+impl warp::reject::Reject for InvalidApiSecret {}
+
+// 校验?secret=或X-API-Secret头是否匹配server.api_secret；未配置则放行
+fn require_api_secret(
+    expected: Option<String>,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::query::<std::collections::HashMap<String, String>>()
+        .or(warp::any().map(std::collections::HashMap::new))
+        .unify()
+        .and(warp::header::optional::<String>("x-api-secret"))
+        .and_then(move |params: std::collections::HashMap<String, String>, header_secret: Option<String>| {
+            let expected = expected.clone();
+            async move {
+                let Some(expected) = expected else {
+                    return Ok(());
+                };
+                let provided = header_secret.or_else(|| params.get("secret").cloned());
+                if provided.as_deref() == Some(expected.as_str()) {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(InvalidApiSecret))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply, std::convert::Infallible> {
+    if err.find::<InvalidApiSecret>().is_some() {
+        Ok(warp::reply::with_status(
+            "Unauthorized",
+            warp::http::StatusCode::UNAUTHORIZED,
+        ))
+    } else {
+        Ok(warp::reply::with_status(
+            "Not Found",
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
 async fn handle_convert_request(
     config: AppConfig,
     converter: TitleConverter,
+    cache: FeedCache,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    match fetch_and_convert_rss(&config.rss.source_url, &converter).await {
+    let ttl = Duration::from_secs(config.cache.cache_ttl_secs);
+
+    if let Some(cached) = cache.read().await.as_ref() {
+        if cached.is_fresh(ttl) {
+            info!("Serving cached RSS feed (age = {:?})", cached.fetched_at.elapsed());
+            return Ok(warp::reply::with_header(
+                cached.body.clone(),
+                "content-type",
+                "text/xml; charset=utf-8",
+            ));
+        }
+    }
+
+    match fetch_and_convert_rss(&config.rss.source_urls, &converter, &config.conversion).await {
         Ok(rss_xml) => {
             info!("Successfully converted RSS feed");
+            *cache.write().await = Some(CachedFeed {
+                body: rss_xml.clone(),
+                fetched_at: Instant::now(),
+            });
             Ok(warp::reply::with_header(
                 rss_xml,
                 "content-type",
@@ -351,35 +828,278 @@ mod tests {
 
     #[test]
     fn test_conan_title_conversion() {
-        let converter = TitleConverter::new();
+        let converter = TitleConverter::from_rules(vec![default_conan_rule()]);
         let original = "[银色子弹字幕组][名侦探柯南][第1170集 食人教室的玄机（后篇）][WEBRIP][简繁日多语MKV][PGS][1080P]";
         let expected = " [银色子弹字幕组] Detective Conan - 1170 (WEBRIP 1080P 简繁日多语MKV) ";
         let result = converter.convert_title(original);
         assert_eq!(result, expected);
     }
-    
+
     #[test]
     fn test_no_match_title() {
-        let converter = TitleConverter::new();
+        let converter = TitleConverter::from_rules(vec![default_conan_rule()]);
         let original = "Some random title that doesn't match";
         let result = converter.convert_title(original);
         assert_eq!(result, original);
     }
-    
+
     #[test]
     fn test_config_loading() {
         let config = AppConfig::default();
         assert_eq!(config.server.host, "127.0.0.1");
         assert_eq!(config.server.port, 3030);
+        assert_eq!(config.rules.len(), 1);
     }
-    
+
+    #[test]
+    fn test_rules_loaded_from_external_config_source_override_the_default() {
+        let toml = r#"
+[[rules]]
+name = "Custom"
+pattern = "Foo (\\d+)"
+replacement = "Bar $1"
+priority = 5
+"#;
+        let config: AppConfig = Config::builder()
+            .add_source(Config::try_from(&AppConfig::default()).unwrap())
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].name, "Custom");
+
+        let converter = TitleConverter::from_rules(config.rules);
+        assert_eq!(converter.convert_title("Foo 42"), "Bar 42");
+    }
+
     #[test]
     fn test_conan_1167_title() {
-        let converter = TitleConverter::new();
+        let converter = TitleConverter::from_rules(vec![default_conan_rule()]);
         let original = " [银色子弹字幕组][名侦探柯南][第1167集 17年前的真相 皇后的谋略][WEBRIP][简繁日多语MKV][1080P] ";
         let result = converter.convert_title(original);
         println!("Original: {}", original);
         println!("Result: {}", result);
         println!("Matched: {}", result != original);
     }
+
+    #[test]
+    fn test_named_capture_group_replacement() {
+        let rule = TitleRule {
+            name: "Named episode".to_string(),
+            pattern: r"Show - (?P<episode>\d+)".to_string(),
+            replacement: "Episode ${episode}".to_string(),
+            priority: 1,
+        };
+        let converter = TitleConverter::from_rules(vec![rule]);
+        let result = converter.convert_title("Show - 42");
+        assert_eq!(result, "Episode 42");
+    }
+
+    fn sample_feed() -> &'static str {
+        r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Test Channel</title>
+<item><title>Show A</title><guid>1</guid></item>
+<item><title>Show B</title><guid>2</guid></item>
+<item><title>Show C</title><guid>3</guid></item>
+</channel></rss>"#
+    }
+
+    #[test]
+    fn test_convert_rss_text_filters_sorts_and_limits() {
+        let rules = vec![
+            TitleRule {
+                name: "B first".to_string(),
+                pattern: r"Show B".to_string(),
+                replacement: "Show B".to_string(),
+                priority: 1,
+            },
+            TitleRule {
+                name: "A second".to_string(),
+                pattern: r"Show A".to_string(),
+                replacement: "Show A".to_string(),
+                priority: 2,
+            },
+        ];
+        let converter = TitleConverter::from_rules(rules);
+        let conversion = ConversionConfig {
+            default_priority: 100,
+            exclude_pattern: Some("Show C".to_string()),
+            max_items: Some(1),
+        };
+
+        let result = convert_rss_text(sample_feed(), &converter, &conversion).unwrap();
+
+        // "Show C" excluded, "Show B" sorted ahead of "Show A" by priority, then limited to 1.
+        assert!(result.contains("Show B"));
+        assert!(!result.contains("Show A"));
+        assert!(!result.contains("Show C"));
+    }
+
+    #[test]
+    fn test_channel_parse_extracts_items() {
+        let channel = Channel::parse(sample_feed()).unwrap();
+        assert_eq!(channel.items.len(), 3);
+        assert_eq!(channel.items[0].original_title, "Show A");
+        assert_eq!(channel.items[0].guid.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_merge_channels_dedupes_across_sources_and_keeps_first_header() {
+        let feed_a = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Source A</title>
+<item><title>Shared</title><guid>dup</guid></item>
+<item><title>Only A</title><guid>a-only</guid></item>
+</channel></rss>"#;
+        let feed_b = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Source B</title>
+<item><title>Shared</title><guid>dup</guid></item>
+<item><title>Only B</title><guid>b-only</guid></item>
+</channel></rss>"#;
+
+        let channel_a = Channel::parse(feed_a).unwrap();
+        let channel_b = Channel::parse(feed_b).unwrap();
+
+        let merged = merge_channels(vec![channel_a.clone(), channel_b]);
+
+        // Items sharing a guid across sources are only kept once.
+        assert_eq!(merged.items.len(), 3);
+        assert_eq!(merged.preamble, channel_a.preamble);
+        assert_eq!(merged.footer, channel_a.footer);
+    }
+
+    #[test]
+    fn test_merge_channels_then_policy_applies_once_on_combined_list() {
+        // Each source is individually under max_items, but the merged feed is not: the policy
+        // must run once on the combined list, not once per source.
+        let feed_a = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Source A</title>
+<item><title>Show A1</title><guid>a1</guid></item>
+<item><title>Show A2</title><guid>a2</guid></item>
+</channel></rss>"#;
+        let feed_b = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel><title>Source B</title>
+<item><title>Show B1</title><guid>b1</guid></item>
+<item><title>Show B2</title><guid>b2</guid></item>
+</channel></rss>"#;
+
+        let channel_a = Channel::parse(feed_a).unwrap();
+        let channel_b = Channel::parse(feed_b).unwrap();
+        let merged = merge_channels(vec![channel_a, channel_b]);
+
+        let converter = TitleConverter::from_rules(vec![]);
+        let conversion = ConversionConfig {
+            default_priority: 0,
+            exclude_pattern: None,
+            max_items: Some(3),
+        };
+
+        let result = render_channel(merged, &converter, &conversion).unwrap();
+        let item_count = result.matches("<item>").count();
+        assert_eq!(item_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_cache_is_served_without_refetching() {
+        let mut config = AppConfig::default();
+        config.rss.source_urls = Vec::new(); // would error if actually refetched
+        let converter = TitleConverter::new();
+        let cache: FeedCache = Arc::new(RwLock::new(Some(CachedFeed {
+            body: "<rss>cached</rss>".to_string(),
+            fetched_at: Instant::now(),
+        })));
+
+        let routes = build_routes(config, converter, cache);
+        let response = warp::test::request().path("/rss.xml").reply(&routes).await;
+
+        assert_eq!(response.status(), warp::http::StatusCode::OK);
+        assert_eq!(response.body(), "<rss>cached</rss>");
+    }
+
+    #[tokio::test]
+    async fn test_expired_cache_triggers_refetch_instead_of_reuse() {
+        let mut config = AppConfig::default();
+        config.rss.source_urls = Vec::new(); // forces a deterministic fetch error instead of a real request
+        let converter = TitleConverter::new();
+        let cache: FeedCache = Arc::new(RwLock::new(Some(CachedFeed {
+            body: "<rss>stale</rss>".to_string(),
+            fetched_at: Instant::now() - Duration::from_secs(config.cache.cache_ttl_secs + 1),
+        })));
+
+        let routes = build_routes(config, converter, cache);
+        let response = warp::test::request().path("/rss.xml").reply(&routes).await;
+
+        // The stale body must not be served as-is; a refetch was attempted (and failed, since
+        // there are no source_urls configured).
+        assert_ne!(response.body(), "<rss>stale</rss>");
+    }
+
+    #[tokio::test]
+    async fn test_require_api_secret_rejects_missing_or_wrong_secret() {
+        let config = AppConfig {
+            server: ServerConfig {
+                host: "127.0.0.1".to_string(),
+                port: 3030,
+                api_secret: Some("s3cr3t".to_string()),
+            },
+            ..AppConfig::default()
+        };
+        let routes = build_routes(config, TitleConverter::new(), Arc::new(RwLock::new(None)));
+
+        let response = warp::test::request().path("/rss.xml").reply(&routes).await;
+        assert_eq!(response.status(), warp::http::StatusCode::UNAUTHORIZED);
+
+        let response = warp::test::request()
+            .path("/rss.xml?secret=wrong")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), warp::http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_require_api_secret_accepts_query_param_or_header() {
+        let config = AppConfig {
+            server: ServerConfig {
+                host: "127.0.0.1".to_string(),
+                port: 3030,
+                api_secret: Some("s3cr3t".to_string()),
+            },
+            rss: RssConfig { source_urls: Vec::new() },
+            ..AppConfig::default()
+        };
+        let routes = build_routes(config.clone(), TitleConverter::new(), Arc::new(RwLock::new(None)));
+
+        let response = warp::test::request()
+            .path("/rss.xml?secret=s3cr3t")
+            .reply(&routes)
+            .await;
+        assert_ne!(response.status(), warp::http::StatusCode::UNAUTHORIZED);
+
+        let routes = build_routes(config, TitleConverter::new(), Arc::new(RwLock::new(None)));
+        let response = warp::test::request()
+            .path("/rss.xml")
+            .header("x-api-secret", "s3cr3t")
+            .reply(&routes)
+            .await;
+        assert_ne!(response.status(), warp::http::StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_read_to_string_from_piped_input() {
+        let content = read_to_string(Cursor::new(sample_feed().as_bytes())).unwrap();
+        assert_eq!(content, sample_feed());
+    }
+
+    #[test]
+    fn test_convert_cli_reads_from_file() {
+        let path = std::env::temp_dir().join(format!("arrs-rss-converter-test-{}.xml", std::process::id()));
+        std::fs::write(&path, sample_feed()).unwrap();
+
+        let result = convert_cli(AppConfig::default(), Some(path.clone()));
+
+        std::fs::remove_file(&path).ok();
+        assert!(result.is_ok());
+    }
 }
\ No newline at end of file